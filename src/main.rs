@@ -1,11 +1,84 @@
 use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
 use actix_cors::Cors;
+use actix_multipart::Multipart;
+use async_stream::stream;
+use futures_util::{StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use reqwest::Client;
 use dotenvy::dotenv;
 
-const GROQ_URL: &str = "https://api.groq.com/openai/v1/chat/completions";
+const GROQ_TRANSCRIPTION_URL: &str = "https://api.groq.com/openai/v1/audio/transcriptions";
+const WHISPER_MODEL: &str = "whisper-large-v3";
+const DEFAULT_PROVIDER: &str = "groq";
+
+/// An OpenAI-compatible chat completions backend: a base URL, the env var
+/// holding its API key, and the models to fall back to when the caller
+/// doesn't name one.
+struct Provider {
+    base_url: String,
+    api_key_env: String,
+    default_model: String,
+    default_vision_model: String,
+}
+
+/// Known providers, seeded with sensible defaults and overridable/extendable
+/// via environment variables so a deployment can point at any OpenAI-compatible
+/// endpoint without a code change.
+fn load_providers() -> HashMap<String, Provider> {
+    let mut providers = HashMap::new();
+    providers.insert(
+        "groq".to_string(),
+        Provider {
+            base_url: "https://api.groq.com/openai/v1/chat/completions".to_string(),
+            api_key_env: "GROQ_API_KEY".to_string(),
+            default_model: "deepseek-r1-distill-llama-70b".to_string(),
+            default_vision_model: "meta-llama/llama-4-scout-17b-16e-instruct".to_string(),
+        },
+    );
+    providers.insert(
+        "openai".to_string(),
+        Provider {
+            base_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            api_key_env: "OPENAI_API_KEY".to_string(),
+            default_model: "gpt-4o-mini".to_string(),
+            default_vision_model: "gpt-4o-mini".to_string(),
+        },
+    );
+
+    // Additional OpenAI-compatible endpoints, configured as:
+    //   EXTRA_PROVIDERS=myhost
+    //   MYHOST_BASE_URL=https://.../chat/completions
+    //   MYHOST_API_KEY_ENV=MYHOST_API_KEY
+    //   MYHOST_DEFAULT_MODEL=...
+    if let Ok(names) = env::var("EXTRA_PROVIDERS") {
+        for name in names.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+            let prefix = name.to_uppercase();
+            let base_url = match env::var(format!("{}_BASE_URL", prefix)) {
+                Ok(url) => url,
+                Err(_) => continue,
+            };
+            let api_key_env = env::var(format!("{}_API_KEY_ENV", prefix))
+                .unwrap_or_else(|_| format!("{}_API_KEY", prefix));
+            let default_model = env::var(format!("{}_DEFAULT_MODEL", prefix))
+                .unwrap_or_else(|_| "gpt-4o-mini".to_string());
+            let default_vision_model = env::var(format!("{}_DEFAULT_VISION_MODEL", prefix))
+                .unwrap_or_else(|_| default_model.clone());
+            providers.insert(
+                name.to_lowercase(),
+                Provider {
+                    base_url,
+                    api_key_env,
+                    default_model,
+                    default_vision_model,
+                },
+            );
+        }
+    }
+
+    providers
+}
 
 #[derive(Deserialize, Debug)]
 struct UserRequest {
@@ -13,12 +86,29 @@ struct UserRequest {
     message: Option<String>,
     image_url: Option<String>,
     image_base64: Option<String>,
+    stream: Option<bool>,
+    provider: Option<String>,
+    model: Option<String>,
+    temperature: Option<f64>,
+    max_tokens: Option<u32>,
+    tools: Option<serde_json::Value>,
+    tool_choice: Option<serde_json::Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct Message {
     role: String,
-    content: serde_json::Value, // Use serde_json::Value to handle both string and array
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<serde_json::Value>, // Use serde_json::Value to handle both string and array
+    // Carried so a tool round-trip can be replayed: an assistant message forwards
+    // the `tool_calls` Groq asked for, and a follow-up `tool` message answers one
+    // of them via `tool_call_id`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -27,11 +117,68 @@ struct ApiPayload {
     messages: Vec<Message>,
     temperature: f64,
     max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<serde_json::Value>,
 }
 
 #[derive(Serialize)]
 struct ChatResponse {
     reply: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<serde_json::Value>,
+}
+
+/// A machine-readable error body returned from every `chat` failure branch,
+/// so clients can switch on `code` instead of matching free-text messages.
+#[derive(Serialize)]
+struct ApiError {
+    code: String,
+    message: String,
+    upstream_status: Option<u16>,
+}
+
+impl ApiError {
+    fn new(code: &str, message: impl Into<String>) -> Self {
+        ApiError {
+            code: code.to_string(),
+            message: message.into(),
+            upstream_status: None,
+        }
+    }
+
+    fn upstream(message: impl Into<String>, status: reqwest::StatusCode) -> Self {
+        ApiError {
+            code: "UPSTREAM_ERROR".to_string(),
+            message: message.into(),
+            upstream_status: Some(status.as_u16()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReplyChunk {
+    reply_chunk: String,
+}
+
+/// Emitted instead of `ReplyChunk` when a streamed delta carries tool calls
+/// rather than plain text content.
+#[derive(Serialize)]
+struct ToolCallChunk {
+    tool_calls: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct GroqTranscription {
+    text: String,
 }
 
 #[derive(Deserialize)]
@@ -41,7 +188,8 @@ struct GroqChoice {
 
 #[derive(Deserialize)]
 struct GroqMessage {
-    content: String,
+    content: Option<String>,
+    tool_calls: Option<serde_json::Value>,
 }
 
 #[derive(Deserialize)]
@@ -49,6 +197,22 @@ struct GroqResponse {
     choices: Vec<GroqChoice>,
 }
 
+#[derive(Deserialize)]
+struct GroqStreamChoice {
+    delta: GroqDelta,
+}
+
+#[derive(Deserialize)]
+struct GroqDelta {
+    content: Option<String>,
+    tool_calls: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct GroqStreamChunk {
+    choices: Vec<GroqStreamChoice>,
+}
+
 #[get("/")]
 async fn home() -> impl Responder {
     HttpResponse::Ok().body("🧠 Groq Unified Chat + Vision API is running!")
@@ -57,23 +221,65 @@ async fn home() -> impl Responder {
 #[post("/chat")]
 async fn chat(req: web::Json<UserRequest>) -> HttpResponse {
     dotenv().ok();
-    let api_key = match env::var("GROQ_API_KEY") {
+
+    let providers = load_providers();
+    let provider_name = req
+        .provider
+        .clone()
+        .unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+    let Some(provider) = providers.get(&provider_name.to_lowercase()) else {
+        return HttpResponse::BadRequest().json(ApiError::new(
+            "INVALID_INPUT",
+            format!("Unknown provider '{}'", provider_name),
+        ));
+    };
+
+    let api_key = match env::var(&provider.api_key_env) {
         Ok(key) => key,
-        Err(_) => return HttpResponse::InternalServerError().json("API key not found"),
+        Err(_) => {
+            return HttpResponse::InternalServerError()
+                .json(ApiError::new("MISSING_API_KEY", "API key not found"))
+        }
     };
 
+    if let Some(temperature) = req.temperature {
+        if !(0.0..=2.0).contains(&temperature) {
+            return HttpResponse::BadRequest().json(ApiError::new(
+                "INVALID_INPUT",
+                "temperature must be between 0.0 and 2.0",
+            ));
+        }
+    }
+    if let Some(max_tokens) = req.max_tokens {
+        if max_tokens == 0 {
+            return HttpResponse::BadRequest()
+                .json(ApiError::new("INVALID_INPUT", "max_tokens must be greater than 0"));
+        }
+    }
+
     let client = Client::new();
+    let wants_stream = req.stream.unwrap_or(false);
+    let is_vision = req.image_url.is_some() || req.image_base64.is_some();
+    let model = req.model.clone().unwrap_or_else(|| {
+        if is_vision {
+            provider.default_vision_model.clone()
+        } else {
+            provider.default_model.clone()
+        }
+    });
+    let temperature = req.temperature.unwrap_or(0.5);
+    let max_tokens = req.max_tokens.unwrap_or(1024);
 
     // Determine the API payload based on the request
     let payload = if let Some(message) = &req.message {
         // Handle Vision or Single-turn text
-        if req.image_url.is_some() || req.image_base64.is_some() {
+        if is_vision {
             let image_data = if let Some(url) = &req.image_url {
                 serde_json::json!({"url": url})
             } else {
                 serde_json::json!({"url": req.image_base64})
             };
-            
+
             let content = serde_json::json!([
                 {"type": "text", "text": message},
                 {"type": "image_url", "image_url": image_data}
@@ -81,43 +287,59 @@ async fn chat(req: web::Json<UserRequest>) -> HttpResponse {
 
             let messages = vec![Message {
                 role: "user".to_string(),
-                content: content,
+                content: Some(content),
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
             }];
 
             ApiPayload {
-                model: "meta-llama/llama-4-scout-17b-16e-instruct".to_string(),
+                model,
                 messages,
-                temperature: 0.5,
-                max_tokens: 1024,
+                temperature,
+                max_tokens,
+                stream: wants_stream.then_some(true),
+                tools: req.tools.clone(),
+                tool_choice: req.tool_choice.clone(),
             }
         } else {
             // Single-turn text
             let messages = vec![Message {
                 role: "user".to_string(),
-                content: serde_json::Value::String(message.clone()),
+                content: Some(serde_json::Value::String(message.clone())),
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
             }];
 
             ApiPayload {
-                model: "deepseek-r1-distill-llama-70b".to_string(),
+                model,
                 messages,
-                temperature: 0.5,
-                max_tokens: 1024,
+                temperature,
+                max_tokens,
+                stream: wants_stream.then_some(true),
+                tools: req.tools.clone(),
+                tool_choice: req.tool_choice.clone(),
             }
         }
     } else if let Some(messages) = &req.messages {
         // Multi-turn text
         ApiPayload {
-            model: "deepseek-r1-distill-llama-70b".to_string(),
+            model,
             messages: messages.clone(),
-            temperature: 0.5,
-            max_tokens: 1024,
+            temperature,
+            max_tokens,
+            stream: wants_stream.then_some(true),
+            tools: req.tools.clone(),
+            tool_choice: req.tool_choice.clone(),
         }
     } else {
-        return HttpResponse::BadRequest().json("No valid input provided");
+        return HttpResponse::BadRequest()
+            .json(ApiError::new("INVALID_INPUT", "No valid input provided"));
     };
 
     // Make the API call
-    let res = match client.post(GROQ_URL)
+    let res = match client.post(&provider.base_url)
         .header("Authorization", format!("Bearer {}", api_key))
         .json(&payload)
         .timeout(std::time::Duration::from_secs(30))
@@ -125,30 +347,205 @@ async fn chat(req: web::Json<UserRequest>) -> HttpResponse {
         .await {
             Ok(r) => r,
             Err(e) => {
-                let error_message = format!("Groq API failed: {}", e);
-                return HttpResponse::InternalServerError().json(error_message);
+                let error_message = format!("{} API failed: {}", provider_name, e);
+                return HttpResponse::InternalServerError()
+                    .json(ApiError::new("UPSTREAM_ERROR", error_message));
             }
         };
 
+    if !res.status().is_success() {
+        let status = res.status();
+        let body = res.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
+        let error_message = format!("{} API returned an error: {} - {}", provider_name, status, body);
+        return HttpResponse::build(status).json(ApiError::upstream(error_message, status));
+    }
+
+    if wants_stream {
+        return HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .streaming(groq_event_stream(res));
+    }
+
     // Parse and return the response
-    if res.status().is_success() {
-        match res.json::<GroqResponse>().await {
-            Ok(groq_res) => {
-                if let Some(choice) = groq_res.choices.get(0) {
-                    HttpResponse::Ok().json(ChatResponse {
-                        reply: choice.message.content.trim().to_string(),
-                    })
+    match res.json::<GroqResponse>().await {
+        Ok(groq_res) => {
+            if let Some(choice) = groq_res.choices.get(0) {
+                HttpResponse::Ok().json(ChatResponse {
+                    reply: choice
+                        .message
+                        .content
+                        .as_deref()
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                    tool_calls: choice.message.tool_calls.clone(),
+                })
+            } else {
+                HttpResponse::InternalServerError()
+                    .json(ApiError::new("PARSE_ERROR", "No choices found in API response"))
+            }
+        }
+        Err(_) => HttpResponse::InternalServerError()
+            .json(ApiError::new("PARSE_ERROR", "Failed to parse API response")),
+    }
+}
+
+#[post("/transcribe")]
+async fn transcribe(mut payload: Multipart) -> HttpResponse {
+    dotenv().ok();
+    let api_key = match env::var("GROQ_API_KEY") {
+        Ok(key) => key,
+        Err(_) => return HttpResponse::InternalServerError().json("API key not found"),
+    };
+
+    let mut audio: Option<(String, Vec<u8>)> = None;
+    let mut language: Option<String> = None;
+    let mut prompt: Option<String> = None;
+
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = match field.content_disposition().get_name() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        match name.as_str() {
+            "file" | "audio" => {
+                let filename = field
+                    .content_disposition()
+                    .get_filename()
+                    .unwrap_or("audio.wav")
+                    .to_string();
+                let mut bytes = Vec::new();
+                while let Some(chunk) = field.next().await {
+                    let chunk = match chunk {
+                        Ok(c) => c,
+                        Err(e) => {
+                            return HttpResponse::BadRequest()
+                                .json(format!("Failed to read audio upload: {}", e))
+                        }
+                    };
+                    bytes.extend_from_slice(&chunk);
+                }
+                audio = Some((filename, bytes));
+            }
+            "language" | "prompt" => {
+                let mut text = Vec::new();
+                while let Some(chunk) = field.next().await {
+                    match chunk {
+                        Ok(c) => text.extend_from_slice(&c),
+                        Err(e) => {
+                            return HttpResponse::BadRequest()
+                                .json(format!("Failed to read field '{}': {}", name, e))
+                        }
+                    }
+                }
+                let text = String::from_utf8_lossy(&text).to_string();
+                if name == "language" {
+                    language = Some(text);
                 } else {
-                    HttpResponse::InternalServerError().json("No choices found in API response")
+                    prompt = Some(text);
                 }
             }
-            Err(_) => HttpResponse::InternalServerError().json("Failed to parse API response"),
+            _ => {}
         }
-    } else {
+    }
+
+    let Some((filename, bytes)) = audio else {
+        return HttpResponse::BadRequest().json("No audio file provided");
+    };
+
+    let mut form = reqwest::multipart::Form::new()
+        .part("file", reqwest::multipart::Part::bytes(bytes).file_name(filename))
+        .text("model", WHISPER_MODEL);
+    if let Some(language) = language {
+        form = form.text("language", language);
+    }
+    if let Some(prompt) = prompt {
+        form = form.text("prompt", prompt);
+    }
+
+    let client = Client::new();
+    let res = match client
+        .post(GROQ_TRANSCRIPTION_URL)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .multipart(form)
+        .timeout(std::time::Duration::from_secs(60))
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            let error_message = format!("Groq API failed: {}", e);
+            return HttpResponse::InternalServerError().json(error_message);
+        }
+    };
+
+    if !res.status().is_success() {
         let status = res.status();
         let body = res.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
         let error_message = format!("Groq API returned an error: {} - {}", status, body);
-        HttpResponse::Status(status).json(error_message)
+        return HttpResponse::build(status).json(error_message);
+    }
+
+    match res.json::<GroqTranscription>().await {
+        Ok(transcription) => HttpResponse::Ok().json(TranscriptionResponse {
+            text: transcription.text,
+        }),
+        Err(_) => HttpResponse::InternalServerError().json("Failed to parse API response"),
+    }
+}
+
+/// Relays Groq's `text/event-stream` output to the client, re-emitting each
+/// `delta.content` fragment as a `{"reply_chunk": "..."}` SSE event.
+fn groq_event_stream(
+    res: reqwest::Response,
+) -> impl futures_util::Stream<Item = Result<web::Bytes, actix_web::Error>> {
+    stream! {
+        let mut body = res.bytes_stream();
+        let mut buf = Vec::new();
+
+        while let Some(chunk) = body.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(actix_web::error::ErrorInternalServerError(format!(
+                        "Groq stream failed: {}",
+                        e
+                    )));
+                    return;
+                }
+            };
+            buf.extend_from_slice(&chunk);
+
+            while let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                let Some(data) = line.strip_prefix("data:") else { continue };
+                let data = data.trim();
+
+                if data == "[DONE]" {
+                    return;
+                }
+                if data.is_empty() {
+                    continue;
+                }
+
+                let parsed: Result<GroqStreamChunk, _> = serde_json::from_str(data);
+                if let Ok(chunk) = parsed {
+                    let delta = chunk.choices.get(0).map(|c| &c.delta);
+                    if let Some(content) = delta.and_then(|d| d.content.clone()) {
+                        let event = serde_json::to_string(&ReplyChunk { reply_chunk: content })
+                            .unwrap_or_default();
+                        yield Ok(web::Bytes::from(format!("data: {}\n\n", event)));
+                    } else if let Some(tool_calls) = delta.and_then(|d| d.tool_calls.clone()) {
+                        let event = serde_json::to_string(&ToolCallChunk { tool_calls })
+                            .unwrap_or_default();
+                        yield Ok(web::Bytes::from(format!("data: {}\n\n", event)));
+                    }
+                }
+            }
+        }
     }
 }
 
@@ -160,6 +557,7 @@ async fn main() -> std::io::Result<()> {
             .wrap(cors)
             .service(home)
             .service(chat)
+            .service(transcribe)
     })
     .bind(("0.0.0.0", 10000))?
     .run()